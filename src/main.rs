@@ -1,12 +1,24 @@
 use serenity::async_trait;
 use serenity::model::channel::Message;
 use serenity::model::gateway::Ready;
-use serenity::builder::CreateMessage;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::model::webhook::Webhook;
+use serenity::model::Timestamp;
+use serenity::builder::{CreateEmbed, CreateMessage, CreateWebhook, ExecuteWebhook};
 use serenity::prelude::*;
-use std::collections::HashMap;
+use fancy_regex::Regex;
+use log::{debug, error, info, warn, LevelFilter};
+use log4rs::append::console::ConsoleAppender;
+use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
+use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
+use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
+use log4rs::append::rolling_file::RollingFileAppender;
+use log4rs::config::{Appender, Config, Root};
+use log4rs::encode::pattern::PatternEncoder;
+use std::collections::{HashMap, VecDeque};
 use std::path::Path;
 use std::sync::RwLock;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
 
 const ENV_PATHS: &[&'static str] = &[
@@ -18,107 +30,370 @@ const APP_DATA_FILE: &str = "appdata.bin";
 
 const APP_WORD_LIST: &str = include_str!("../wordlist.txt");
 
+// Root is always node index 0.
+const TRIE_ROOT: usize = 0;
+
 #[derive(Debug, Default)]
 struct TrieNode {
-	children: HashMap<char, TrieNode>,
+	children: HashMap<char, usize>,
+	fail: usize,
+	depth: usize,
 	end: bool,
+	// Lengths (in chars) of every word that ends at this state, including
+	// words inherited through fail links (i.e. words that are a suffix of
+	// whatever was just scanned).
+	output: Vec<usize>,
 }
 
-#[derive(Debug, Default)]
+// Aho-Corasick automaton over an arena of nodes, so a message is scanned in
+// a single O(n + matches) pass instead of restarting the traversal at every
+// offset. Call `compute_fail_links` once after all words are inserted and
+// before the first `find_matches`/`find_word` call.
+#[derive(Debug)]
 struct Trie {
-	root: TrieNode,
+	nodes: Vec<TrieNode>,
 }
 
-impl Trie {
-	fn reset(&mut self) {
-		self.root = TrieNode::default();
+impl Default for Trie {
+	fn default() -> Self {
+		Trie { nodes: vec![TrieNode::default()] }
 	}
+}
 
+impl Trie {
 	fn insert(&mut self, word: &str) {
 		if word.len() == 0 {
 			return;
 		}
-		let mut node= &mut self.root;
+		let mut cur = TRIE_ROOT;
 		for c in word.chars() {
-			if !node.children.contains_key(&c) {
-				node.children.insert(c, TrieNode::default());
+			cur = match self.nodes[cur].children.get(&c) {
+				Some(&next) => next,
+				None => {
+					let depth = self.nodes[cur].depth + 1;
+					self.nodes.push(TrieNode { depth, ..TrieNode::default() });
+					let next = self.nodes.len() - 1;
+					self.nodes[cur].children.insert(c, next);
+					next
+				}
+			};
+		}
+		let depth = self.nodes[cur].depth;
+		if !self.nodes[cur].end {
+			self.nodes[cur].end = true;
+			self.nodes[cur].output.push(depth);
+		}
+	}
+
+	// BFS from the root: direct children fail back to the root, and every
+	// other node's fail link is the longest proper suffix of its path that
+	// is also a path in the trie. Output sets are unioned along the way so
+	// words that are suffixes of other matched words are still reported.
+	fn compute_fail_links(&mut self) {
+		let mut queue = VecDeque::new();
+		let root_children: Vec<(char, usize)> =
+			self.nodes[TRIE_ROOT].children.iter().map(|(&c, &i)| (c, i)).collect();
+		for (_, child) in root_children {
+			self.nodes[child].fail = TRIE_ROOT;
+			queue.push_back(child);
+		}
+		while let Some(node) = queue.pop_front() {
+			let children: Vec<(char, usize)> =
+				self.nodes[node].children.iter().map(|(&c, &i)| (c, i)).collect();
+			for (c, child) in children {
+				let mut fallback = self.nodes[node].fail;
+				while fallback != TRIE_ROOT && !self.nodes[fallback].children.contains_key(&c) {
+					fallback = self.nodes[fallback].fail;
+				}
+				let fail = self.nodes[fallback].children.get(&c).copied()
+					.filter(|&n| n != child)
+					.unwrap_or(TRIE_ROOT);
+				self.nodes[child].fail = fail;
+				let inherited = self.nodes[fail].output.clone();
+				self.nodes[child].output.extend(inherited);
+				queue.push_back(child);
 			}
-			node = node.children.get_mut(&c).unwrap();
 		}
-		node.end = true;
 	}
 
 	fn find_matches(&self, input: &str) -> Vec<(usize, usize)> {
 		let mut matches = Vec::<(usize, usize)>::default();
-		let mut cursor_it = input.chars();
-		let mut local_it = cursor_it.clone();
-		for start in 0..input.len() {
-			// traverse the tree with the local iterator
-			let mut node= &self.root;
-			let mut end = 0;
+		let mut state = TRIE_ROOT;
+		for (i, c) in input.chars().enumerate() {
 			loop {
-				match local_it.next() {
-					Some(c) => {
-						let v = node.children.get(&c);
-						if v.is_some() {
-							node = v.unwrap();
-							if node.end {
-								matches.push((start, start + end));
-							}
-							end += 1;
-						}
-						else {
-							break
-						}
-					}
-					None => {
-						break
-					}
+				if let Some(&next) = self.nodes[state].children.get(&c) {
+					state = next;
+					break;
+				} else if state == TRIE_ROOT {
+					break;
+				} else {
+					state = self.nodes[state].fail;
 				}
 			}
-			cursor_it.next();
-			local_it = cursor_it.clone();
+			let end = i + 1;
+			for &len in &self.nodes[state].output {
+				matches.push((end - len, end));
+			}
 		}
 		matches
 	}
 
 	fn find_word(&self, input: &str) -> bool {
-		let mut node = &self.root;
+		let mut node = TRIE_ROOT;
 		for c in input.chars() {
-			if let Some(pair) = node.children.get_key_value(&c) {
-				node = pair.1;
-				if node.end {
-					return true;
+			match self.nodes[node].children.get(&c) {
+				Some(&next) => {
+					node = next;
+					if self.nodes[node].end {
+						return true;
+					}
 				}
-				continue
+				None => return false,
 			}
-			return false;
 		}
 		false
 	}
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-struct AppData {
+// Characters with no visible glyph that are commonly stuffed between letters
+// to dodge literal matching (e.g. "b<ZWSP>a<ZWSP>d").
+fn is_zero_width(c: char) -> bool {
+	matches!(c, '\u{200B}'..='\u{200D}' | '\u{FEFF}' | '\u{2060}')
+}
+
+// Combining diacritical marks that can be layered onto a base letter
+// ("z\u{0336}a\u{0301}lgo" text) without changing how the word reads.
+fn is_combining_mark(c: char) -> bool {
+	matches!(c,
+		'\u{0300}'..='\u{036F}' |
+		'\u{1AB0}'..='\u{1AFF}' |
+		'\u{1DC0}'..='\u{1DFF}' |
+		'\u{20D0}'..='\u{20FF}' |
+		'\u{FE20}'..='\u{FE2F}')
+}
+
+fn default_leet_map() -> HashMap<char, char> {
+	HashMap::from([
+		('4', 'a'), ('@', 'a'),
+		('3', 'e'),
+		('1', 'i'), ('!', 'i'),
+		('0', 'o'),
+		('$', 's'), ('5', 's'),
+		('7', 't'),
+	])
+}
+
+// Normalizes `input` into a lowercased string with zero-width and combining
+// characters stripped, leet substitutions folded per `leet_map`, and runs of
+// 3+ identical characters collapsed to one, so obfuscated spellings like
+// "b4d", "b​a​d", and "baaaad" all normalize to "bad". Alongside the
+// normalized string this returns a map from each of its char offsets back to
+// the byte offset of the corresponding character in `input`, so spans found
+// by `Trie::find_matches` on the normalized text can be translated back to
+// the real message.
+fn normalize(input: &str, leet_map: &HashMap<char, char>) -> (String, Vec<usize>) {
+	let mut folded: Vec<(char, usize)> = Vec::new();
+	for (byte_idx, orig_c) in input.char_indices() {
+		// `char::to_lowercase` can expand to more than one char (e.g. 'İ' ->
+		// "i\u{307}"); tag every char it produces with `byte_idx`, the byte
+		// offset of the *original* char in `input`, so the offset map below
+		// always points at a real char boundary in `input` regardless of
+		// how lowercasing changes byte length.
+		for c in orig_c.to_lowercase() {
+			if is_zero_width(c) || is_combining_mark(c) {
+				continue;
+			}
+			folded.push((leet_map.get(&c).copied().unwrap_or(c), byte_idx));
+		}
+	}
+	let mut collapsed: Vec<(char, usize)> = Vec::new();
+	let mut i = 0;
+	while i < folded.len() {
+		let (c, byte_idx) = folded[i];
+		let mut run_end = i + 1;
+		while run_end < folded.len() && folded[run_end].0 == c {
+			run_end += 1;
+		}
+		if run_end - i >= 3 {
+			collapsed.push((c, byte_idx));
+		} else {
+			collapsed.extend_from_slice(&folded[i..run_end]);
+		}
+		i = run_end;
+	}
+	(collapsed.iter().map(|&(c, _)| c).collect(), collapsed.iter().map(|&(_, b)| b).collect())
+}
+
+// Translates a `(start, end)` char-offset span of a normalized string back
+// into a byte span of the original, pre-normalization string using the
+// offset map returned alongside it by `normalize`.
+fn original_span(offsets: &[usize], original: &str, start: usize, end: usize) -> (usize, usize) {
+	let start_byte = offsets.get(start).copied().unwrap_or(original.len());
+	let end_byte = offsets.get(end).copied().unwrap_or(original.len());
+	(start_byte, end_byte)
+}
+
+fn default_mask() -> String {
+	"████".to_string()
+}
+
+// Replaces every offending (normalized char-offset) span in `original` with
+// `mask`, merging overlapping/adjacent spans so a mask is never printed
+// twice over the same text.
+fn mask_spans(original: &str, mask: &str, offsets: &[usize], spans: &[(usize, usize)]) -> String {
+	let mut byte_spans: Vec<(usize, usize)> = spans.iter()
+		.map(|&(start, end)| original_span(offsets, original, start, end))
+		.collect();
+	byte_spans.sort();
+	let mut merged: Vec<(usize, usize)> = Vec::new();
+	for span in byte_spans {
+		match merged.last_mut() {
+			Some(last) if span.0 <= last.1 => last.1 = last.1.max(span.1),
+			_ => merged.push(span),
+		}
+	}
+	let mut result = String::new();
+	let mut cursor = 0;
+	for (start, end) in merged {
+		result.push_str(&original[cursor..start]);
+		result.push_str(mask);
+		cursor = end;
+	}
+	result.push_str(&original[cursor..]);
+	result
+}
+
+// A rung on a guild's escalation ladder: when this is the rung with the
+// highest `level` at or below a user's un-decayed offense count, `action`
+// is applied (in addition to the usual delete + notify).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct PunishStep {
+	level: u32,
+	action: PunishAction,
+	duration_minutes: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum PunishAction {
+	Warn,
+	Timeout,
+	Kick,
+}
+
+impl PunishAction {
+	fn parse(s: &str) -> Option<PunishAction> {
+		match s.to_lowercase().as_str() {
+			"warn" => Some(PunishAction::Warn),
+			"timeout" => Some(PunishAction::Timeout),
+			"kick" => Some(PunishAction::Kick),
+			_ => None,
+		}
+	}
+}
+
+fn default_ladder() -> Vec<PunishStep> {
+	vec![
+		PunishStep { level: 1, action: PunishAction::Warn, duration_minutes: 0 },
+		PunishStep { level: 3, action: PunishAction::Timeout, duration_minutes: 10 },
+		PunishStep { level: 5, action: PunishAction::Kick, duration_minutes: 0 },
+	]
+}
+
+// A single guild's moderation settings. Every server the bot joins keeps its
+// own word list, admins, and allow/deny overrides instead of sharing one
+// global list.
+#[derive(Serialize, Deserialize, Debug)]
+struct GuildConfig {
 	words: Vec<String>,
 	admins: Vec<(String, u64)>,
+	#[serde(default = "default_leet_map")]
+	leet_map: HashMap<char, char>,
+	// Dictionary words (from APP_WORD_LIST) this guild explicitly wants
+	// allowed even if a censor word matches inside them.
+	#[serde(default)]
+	allow_overrides: Vec<String>,
+	// Dictionary words this guild wants excluded from the allow list even
+	// though no censor word matches inside them.
+	#[serde(default)]
+	deny_overrides: Vec<String>,
+	// When set, a flagged message is re-posted through a webhook with the
+	// offending spans masked instead of just being deleted.
+	#[serde(default)]
+	mask_enabled: bool,
+	#[serde(default = "default_mask")]
+	mask_str: String,
+	// Channel moderation actions get posted to as an audit trail.
+	#[serde(default)]
+	log_channel: Option<u64>,
+	// Escalation ladder: the rung with the highest `level` at or below a
+	// user's current offense count is the action applied.
+	#[serde(default = "default_ladder")]
+	ladder: Vec<PunishStep>,
+	// Seconds since a user's last offense after which their count decays
+	// back to zero. `None` means offenses never decay.
+	#[serde(default)]
+	offense_window_secs: Option<u64>,
+	// Regex rules (anchors, word boundaries, lookarounds) alongside the
+	// literal `words` list, for cases the Trie over-blocks (e.g. a banned
+	// word appearing inside an innocent one).
+	#[serde(default)]
+	regex_patterns: Vec<String>,
+}
+
+impl Default for GuildConfig {
+	fn default() -> Self {
+		GuildConfig {
+			words: Vec::default(),
+			admins: Vec::default(),
+			leet_map: default_leet_map(),
+			allow_overrides: Vec::default(),
+			deny_overrides: Vec::default(),
+			mask_enabled: false,
+			mask_str: default_mask(),
+			log_channel: None,
+			ladder: default_ladder(),
+			offense_window_secs: None,
+			regex_patterns: Vec::default(),
+		}
+	}
 }
 
-impl AppData {
+impl GuildConfig {
 	fn build_trie(&self) -> Trie {
 		let mut trie = Trie::default();
 		for word in self.words.iter() {
-			trie.insert(word.to_lowercase().as_str());
+			let (normalized, _) = normalize(word, &self.leet_map);
+			trie.insert(normalized.as_str());
 		}
+		trie.compute_fail_links();
 		trie
 	}
 }
 
+// A user's offense history within a single guild.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+struct Offense {
+	count: u32,
+	last_offense_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct AppData {
+	guilds: HashMap<u64, GuildConfig>,
+	// Bot owners: admins in every guild, independent of per-guild admin lists.
+	owners: Vec<(String, u64)>,
+	// Keyed by (guild id, user id).
+	#[serde(default)]
+	offenses: HashMap<(u64, u64), Offense>,
+}
+
 #[derive(Default)]
 struct Handler {
-	censor_list: RwLock<Trie>,
-	allow_list: RwLock<Trie>,
+	censor_lists: RwLock<HashMap<u64, Trie>>,
+	allow_lists: RwLock<HashMap<u64, Trie>>,
+	regex_lists: RwLock<HashMap<u64, Vec<Regex>>>,
 	app_data: RwLock<AppData>,
+	webhooks: RwLock<HashMap<ChannelId, Webhook>>,
 }
 
 #[async_trait]
@@ -139,24 +414,42 @@ impl EventHandler for Handler {
 			say += "d!admin {mention} - add an administrator\n";
 			say += "d!remove {number} - remove a banned word from the list\n";
 			say += "d!add {word} - add a banned word to the list\n";
+			say += "d!log - set this channel as the moderation audit log\n";
+			say += "d!punish {level} {warn|timeout|kick} {minutes} - edit the escalation ladder\n";
+			say += "d!offenses {mention} - show a user's offense count\n";
+			say += "d!pardon {mention} - reset a user's offense count\n";
+			say += "d!addre {pattern} - add a regex banned-phrase rule\n";
+			say += "d!mask {on|off|set {mask}} - toggle webhook re-posting with masked spans, or set the mask string\n";
 			say += "```";
 			if let Err(why) = msg.channel_id.say(&ctx.http, say).await {
-				println!("Error listing banned words: {why:?}");
+				warn!("Error listing banned words: {why:?}");
 			}
 			return;
 		}
+		// everything below here is per-guild; ignore DMs and other guildless contexts
+		let guild_id = match msg.guild_id {
+			Some(id) => id.get(),
+			None => return,
+		};
 		if msg.content.eq("d!list") {
-			self.say_list(&ctx, &msg, false).await;
+			self.say_list(&ctx, &msg, guild_id, false).await;
 			return;
 		}
-		// in the list of admins
-		if self.app_data.read().unwrap().admins.iter().find(|x| x.1 == msg.author.id.get()).is_some() {
+		// bot owners are admins everywhere; guild admins are scoped to their own guild
+		let is_admin = {
+			let app_data = self.app_data.read().unwrap();
+			app_data.owners.iter().any(|x| x.1 == msg.author.id.get())
+				|| app_data.guilds.get(&guild_id)
+					.is_some_and(|g| g.admins.iter().any(|x| x.1 == msg.author.id.get()))
+		};
+		if is_admin {
 			if msg.content.starts_with("d!admin") {
 				for user in &msg.mentions {
-					self.app_data.write().unwrap().admins.push((user.name.clone(), user.id.get()));
+					self.app_data.write().unwrap().guilds.entry(guild_id).or_default()
+						.admins.push((user.name.clone(), user.id.get()));
 				}
 				self.save();
-				self.say_list(&ctx, &msg, true).await;
+				self.say_list(&ctx, &msg, guild_id, true).await;
 				return;
 			}
 			if msg.content.starts_with("d!remove ") {
@@ -164,44 +457,215 @@ impl EventHandler for Handler {
 				let idx = str::parse::<usize>(&num).unwrap() - 1;
 				{
 					let mut ad = self.app_data.write().unwrap();
-					if idx < ad.words.len() {
-						ad.words.remove(idx);
+					if let Some(g) = ad.guilds.get_mut(&guild_id) {
+						if idx < g.words.len() {
+							g.words.remove(idx);
+						}
 					}
 				}
 				self.save();
-				self.say_list(&ctx, &msg, true).await;
+				self.say_list(&ctx, &msg, guild_id, true).await;
 				return;
 			}
 			if msg.content.starts_with("d!add ") {
 				let phrase = msg.content.replace("d!add ", "");
-				self.app_data.write().unwrap().words.push(phrase);
+				self.app_data.write().unwrap().guilds.entry(guild_id).or_default().words.push(phrase);
+				self.save();
+				self.say_list(&ctx, &msg, guild_id, true).await;
+				return;
+			}
+			if msg.content.eq("d!log") {
+				self.app_data.write().unwrap().guilds.entry(guild_id).or_default()
+					.log_channel = Some(msg.channel_id.get());
 				self.save();
-				self.say_list(&ctx, &msg, true).await;
+				if let Err(why) = msg.channel_id.say(&ctx.http, "This channel is now the moderation audit log.").await {
+					warn!("Error confirming log channel: {why:?}");
+				}
+				return;
+			}
+			if msg.content.starts_with("d!punish ") {
+				let parts: Vec<&str> = msg.content["d!punish ".len()..].split_whitespace().collect();
+				let reply = match parts.as_slice() {
+					[level, action, minutes] => {
+						match (level.parse::<u32>(), PunishAction::parse(action), minutes.parse::<u64>()) {
+							(Ok(level), Some(action), Ok(duration_minutes)) => {
+								let mut app_data = self.app_data.write().unwrap();
+								let config = app_data.guilds.entry(guild_id).or_default();
+								config.ladder.retain(|s| s.level != level);
+								config.ladder.push(PunishStep { level, action, duration_minutes });
+								config.ladder.sort_by_key(|s| s.level);
+								drop(app_data);
+								self.save();
+								format!("Offense {level} now triggers {action:?} ({duration_minutes}m).")
+							}
+							_ => "Usage: d!punish {level} {warn|timeout|kick} {minutes}".to_string(),
+						}
+					}
+					_ => "Usage: d!punish {level} {warn|timeout|kick} {minutes}".to_string(),
+				};
+				if let Err(why) = msg.channel_id.say(&ctx.http, reply).await {
+					warn!("Error confirming ladder update: {why:?}");
+				}
+				return;
+			}
+			if msg.content.starts_with("d!offenses") {
+				if let Some(user) = msg.mentions.first() {
+					let count = self.app_data.read().unwrap().offenses
+						.get(&(guild_id, user.id.get())).map(|o| o.count).unwrap_or(0);
+					if let Err(why) = msg.channel_id.say(&ctx.http, format!("{} has {count} offense(s).", user.name)).await {
+						warn!("Error reporting offenses: {why:?}");
+					}
+				}
+				return;
+			}
+			if msg.content.starts_with("d!pardon") {
+				if let Some(user) = msg.mentions.first() {
+					self.app_data.write().unwrap().offenses.remove(&(guild_id, user.id.get()));
+					self.save();
+					if let Err(why) = msg.channel_id.say(&ctx.http, format!("Cleared offenses for {}.", user.name)).await {
+						warn!("Error pardoning user: {why:?}");
+					}
+				}
+				return;
+			}
+			if msg.content.starts_with("d!mask ") {
+				let parts: Vec<&str> = msg.content["d!mask ".len()..].splitn(2, ' ').collect();
+				let reply = match parts.as_slice() {
+					["on"] => {
+						self.app_data.write().unwrap().guilds.entry(guild_id).or_default()
+							.mask_enabled = true;
+						self.save();
+						"Masking enabled.".to_string()
+					}
+					["off"] => {
+						self.app_data.write().unwrap().guilds.entry(guild_id).or_default()
+							.mask_enabled = false;
+						self.save();
+						"Masking disabled.".to_string()
+					}
+					["set", mask_str] => {
+						self.app_data.write().unwrap().guilds.entry(guild_id).or_default()
+							.mask_str = mask_str.to_string();
+						self.save();
+						format!("Mask string set to `{mask_str}`.")
+					}
+					_ => "Usage: d!mask {on|off|set {mask}}".to_string(),
+				};
+				if let Err(why) = msg.channel_id.say(&ctx.http, reply).await {
+					warn!("Error confirming mask setting: {why:?}");
+				}
+				return;
+			}
+			if msg.content.starts_with("d!addre ") {
+				let pattern = msg.content["d!addre ".len()..].to_string();
+				let reply = match Regex::new(&pattern) {
+					Ok(_) => {
+						self.app_data.write().unwrap().guilds.entry(guild_id).or_default()
+							.regex_patterns.push(pattern.clone());
+						self.save();
+						format!("Added regex rule: `{pattern}`")
+					}
+					Err(why) => format!("Invalid pattern `{pattern}`: {why}"),
+				};
+				if let Err(why) = msg.channel_id.say(&ctx.http, reply).await {
+					warn!("Error confirming regex rule: {why:?}");
+				}
 				return;
 			}
 		}
-		let content = msg.content.to_lowercase();
-		let censors = self.censor_list.read().unwrap().find_matches(content.as_str());
-		if !censors.is_empty() {
-			let allows = self.allow_list.read().unwrap().find_matches(content.as_str());
-			// check the censors to see if there's an allow around it
-			let mut checks = censors.len();
-			for censor in censors.iter() {
-				for allow in allows.iter() {
-					if allow.0 <= censor.0 && allow.1 >= censor.1 {
-						checks -= 1;
-						break;
+		let leet_map = self.app_data.read().unwrap().guilds.get(&guild_id)
+			.map(|g| g.leet_map.clone())
+			.unwrap_or_else(default_leet_map);
+		let (content, offsets) = normalize(&msg.content, &leet_map);
+		// scope the trie locks so they're dropped before we hit any `.await`
+		let mut censors = {
+			let censor_lists = self.censor_lists.read().unwrap();
+			match censor_lists.get(&guild_id) {
+				Some(t) => t.find_matches(content.as_str()),
+				None => return,
+			}
+		};
+		// regex rules share the same allow-list overlap logic as literal matches
+		{
+			let regex_lists = self.regex_lists.read().unwrap();
+			if let Some(patterns) = regex_lists.get(&guild_id) {
+				for pattern in patterns.iter() {
+					for found in pattern.find_iter(content.as_str()).filter_map(Result::ok) {
+						let start = content[..found.start()].chars().count();
+						let end = content[..found.end()].chars().count();
+						censors.push((start, end));
 					}
 				}
 			}
-			if checks > 0 {
+		}
+		if !censors.is_empty() {
+			let allows = {
+				let allow_lists = self.allow_lists.read().unwrap();
+				allow_lists.get(&guild_id)
+					.map(|t| t.find_matches(content.as_str()))
+					.unwrap_or_default()
+			};
+			// check the censors to see if there's an allow around it
+			let offending: Vec<(usize, usize)> = censors.iter()
+				.copied()
+				.filter(|censor| !allows.iter().any(|allow| allow.0 <= censor.0 && allow.1 >= censor.1))
+				.collect();
+			if !offending.is_empty() {
+				info!("Censoring message from {} in guild {}", msg.author.name, guild_id);
 				if let Err(why) = msg.delete(&ctx.http).await {
-					println!("Error deleting message: {why:?}");
+					error!("Error deleting message: {why:?}");
 				}
-				let mut dm_msg = CreateMessage::default();
-				dm_msg = dm_msg.content(format!("Your message was deleted:\n```\n{}\n```", msg.content));
-				if let Err(why) = msg.author.dm(&ctx.http, dm_msg).await {
-					println!("Error deleting message: {why:?}");
+				let flagged: Vec<&str> = offending.iter()
+					.map(|&(start, end)| original_span(&offsets, &msg.content, start, end))
+					.filter_map(|(s, e)| msg.content.get(s..e))
+					.collect();
+				let flagged_text = flagged.join(", ");
+				self.post_audit_log(&ctx, guild_id, &msg, &flagged_text).await;
+				let (mask_enabled, mask_str) = {
+					let app_data = self.app_data.read().unwrap();
+					app_data.guilds.get(&guild_id)
+						.map(|g| (g.mask_enabled, g.mask_str.clone()))
+						.unwrap_or((false, default_mask()))
+				};
+				let mut reposted = false;
+				if mask_enabled {
+					let masked = mask_spans(&msg.content, &mask_str, &offsets, &offending);
+					if let Some(webhook) = self.get_or_create_webhook(&ctx, msg.channel_id).await {
+						let execute = ExecuteWebhook::new()
+							.content(masked)
+							.username(msg.author.name.clone())
+							.avatar_url(msg.author.face());
+						match webhook.execute(&ctx.http, false, execute).await {
+							Ok(_) => reposted = true,
+							Err(why) => warn!("Error reposting sanitized message: {why:?}"),
+						}
+					}
+				}
+				if !reposted {
+					let mut dm_msg = CreateMessage::default();
+					dm_msg = dm_msg.content(format!(
+						"Your message was deleted (flagged: {}):\n```\n{}\n```",
+						flagged_text, msg.content
+					));
+					if let Err(why) = msg.author.dm(&ctx.http, dm_msg).await {
+						error!("Error messaging user about deletion: {why:?}");
+					}
+				}
+				let offense_count = self.record_offense(guild_id, msg.author.id.get());
+				let step = {
+					let app_data = self.app_data.read().unwrap();
+					app_data.guilds.get(&guild_id).and_then(|g| {
+						g.ladder.iter().filter(|s| s.level <= offense_count).max_by_key(|s| s.level).copied()
+					})
+				};
+				match step.map(|s| s.action) {
+					Some(PunishAction::Timeout) => {
+						self.timeout_member(&ctx, guild_id, msg.author.id, step.unwrap().duration_minutes).await;
+					}
+					Some(PunishAction::Kick) => {
+						self.kick_member(&ctx, guild_id, msg.author.id).await;
+					}
+					Some(PunishAction::Warn) | None => {}
 				}
 			}
 		}
@@ -213,11 +677,96 @@ impl EventHandler for Handler {
 	//
 	// In this case, just print what the current user's username is.
 	async fn ready(&self, _: Context, ready: Ready) {
-		println!("{} is connected!", ready.user.name);
+		info!("{} is connected!", ready.user.name);
 	}
 }
 
 impl Handler {
+	// Returns the cached webhook for this channel, creating one lazily
+	// (named after the bot) if none exists yet. Returns `None` if creation
+	// fails, e.g. because the bot lacks the Manage Webhooks permission.
+	async fn get_or_create_webhook(&self, ctx: &Context, channel_id: ChannelId) -> Option<Webhook> {
+		if let Some(webhook) = self.webhooks.read().unwrap().get(&channel_id) {
+			return Some(webhook.clone());
+		}
+		match channel_id.create_webhook(&ctx.http, CreateWebhook::new("Denpabot")).await {
+			Ok(webhook) => {
+				self.webhooks.write().unwrap().insert(channel_id, webhook.clone());
+				Some(webhook)
+			}
+			Err(why) => {
+				warn!("Error creating webhook: {why:?}");
+				None
+			}
+		}
+	}
+
+	// Posts an embed recording a censored message to the guild's configured
+	// audit log channel, if one is set.
+	async fn post_audit_log(&self, ctx: &Context, guild_id: u64, msg: &Message, matched: &str) {
+		let log_channel = {
+			let app_data = self.app_data.read().unwrap();
+			app_data.guilds.get(&guild_id).and_then(|g| g.log_channel)
+		};
+		let log_channel = match log_channel {
+			Some(id) => ChannelId::new(id),
+			None => return,
+		};
+		let embed = CreateEmbed::new()
+			.title("Message censored")
+			.field("Author", format!("{} ({})", msg.author.name, msg.author.id), false)
+			.field("Matched", matched, false)
+			.field("Original content", msg.content.clone(), false)
+			.timestamp(Timestamp::now());
+		let reply = CreateMessage::new().embed(embed);
+		if let Err(why) = log_channel.send_message(&ctx.http, reply).await {
+			warn!("Error posting moderation log: {why:?}");
+		}
+	}
+
+	// Bumps the offense counter for `(guild_id, user_id)`, resetting it
+	// first if the guild has an offense window configured and it's elapsed
+	// since the last offense, and returns the new count.
+	fn record_offense(&self, guild_id: u64, user_id: u64) -> u32 {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+		let mut app_data = self.app_data.write().unwrap();
+		let window = app_data.guilds.get(&guild_id).and_then(|g| g.offense_window_secs);
+		let offense = app_data.offenses.entry((guild_id, user_id)).or_default();
+		if let Some(window) = window {
+			if now.saturating_sub(offense.last_offense_secs) > window {
+				offense.count = 0;
+			}
+		}
+		offense.count += 1;
+		offense.last_offense_secs = now;
+		offense.count
+	}
+
+	async fn timeout_member(&self, ctx: &Context, guild_id: u64, user_id: UserId, duration_minutes: u64) {
+		let until_secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + duration_minutes * 60;
+		let until = match Timestamp::from_unix_timestamp(until_secs as i64) {
+			Ok(t) => t,
+			Err(why) => {
+				warn!("Error building timeout timestamp: {why:?}");
+				return;
+			}
+		};
+		match GuildId::new(guild_id).member(&ctx.http, user_id).await {
+			Ok(mut member) => {
+				if let Err(why) = member.disable_communication_until_datetime(&ctx.http, until).await {
+					warn!("Error timing out member: {why:?}");
+				}
+			}
+			Err(why) => warn!("Error fetching member to time out: {why:?}"),
+		}
+	}
+
+	async fn kick_member(&self, ctx: &Context, guild_id: u64, user_id: UserId) {
+		if let Err(why) = GuildId::new(guild_id).kick(&ctx.http, user_id).await {
+			warn!("Error kicking member: {why:?}");
+		}
+	}
+
 	fn save(&self) {
 		{
 			let app_data_guard = self.app_data.read().unwrap();
@@ -229,12 +778,17 @@ impl Handler {
 
 	fn load(&mut self) {
 		match std::fs::read(APP_DATA_FILE) {
-			Ok(data) => {
-				let mut app_data = self.app_data.write().unwrap();
-				*app_data = serde_cbor::from_slice(&data[..]).unwrap();
-			}
+			Ok(data) => match serde_cbor::from_slice(&data[..]) {
+				Ok(parsed) => {
+					let mut app_data = self.app_data.write().unwrap();
+					*app_data = parsed;
+				}
+				Err(why) => {
+					error!("Incompatible {APP_DATA_FILE}, starting fresh: {why:?}");
+				}
+			},
 			Err(_) => {
-				println!("Failed to load list.dat")
+				warn!("Failed to load {APP_DATA_FILE}")
 			}
 		}
 		self.build();
@@ -242,32 +796,62 @@ impl Handler {
 
 	fn build(&self) {
 		let timer = Instant::now();
-		// rebuild the censor list
+		// rebuild every guild's censor and allow lists
 		let app_data_guard = self.app_data.read().unwrap();
-		*self.censor_list.write().unwrap() = app_data_guard.build_trie();
-		{
-			let cl = self.censor_list.read().unwrap();
-			let mut al = self.allow_list.write().unwrap();
-			al.reset();
+		let mut censor_lists = self.censor_lists.write().unwrap();
+		let mut allow_lists = self.allow_lists.write().unwrap();
+		let mut regex_lists = self.regex_lists.write().unwrap();
+		censor_lists.clear();
+		allow_lists.clear();
+		regex_lists.clear();
+		for (&guild_id, config) in app_data_guard.guilds.iter() {
+			let regexes: Vec<Regex> = config.regex_patterns.iter()
+				.filter_map(|pattern| match Regex::new(pattern) {
+					Ok(re) => Some(re),
+					Err(why) => {
+						warn!("Dropping invalid regex rule `{pattern}` for guild {guild_id}: {why:?}");
+						None
+					}
+				})
+				.collect();
+			regex_lists.insert(guild_id, regexes);
+			let censor = config.build_trie();
+			let mut allow = Trie::default();
 			for word in APP_WORD_LIST.lines() {
-				if cl.find_word(word) {
+				if config.deny_overrides.iter().any(|d| d == word) {
 					continue;
 				}
-				al.insert(word);
+				if censor.find_word(word) {
+					continue;
+				}
+				allow.insert(word);
+			}
+			for word in config.allow_overrides.iter() {
+				let (normalized, _) = normalize(word, &config.leet_map);
+				allow.insert(normalized.as_str());
 			}
+			allow.compute_fail_links();
+			censor_lists.insert(guild_id, censor);
+			allow_lists.insert(guild_id, allow);
 		}
 		let build_time = Instant::now() - timer;
-		println!("It took {} seconds to build the lists", build_time.as_secs_f32());
+		debug!("It took {} seconds to build the lists", build_time.as_secs_f32());
 	}
 
-	async fn say_list(&self, ctx: &Context, msg: &Message, on_update: bool) {
+	async fn say_list(&self, ctx: &Context, msg: &Message, guild_id: u64, on_update: bool) {
 		let mut say: String = String::default();
 		if on_update {
 			say += "Updated!\n";
 		}
+		let (words, admins) = {
+			let app_data = self.app_data.read().unwrap();
+			app_data.guilds.get(&guild_id)
+				.map(|g| (g.words.clone(), g.admins.clone()))
+				.unwrap_or_default()
+		};
 		say += "Banned word list:\n```\n";
 		let mut x = 0;
-		for (i, word) in self.app_data.read().unwrap().words.iter().enumerate() {
+		for (i, word) in words.iter().enumerate() {
 			let n = i + 1;
 			say += format!("{n}. {word}\n").as_str();
 			x += 1;
@@ -278,7 +862,7 @@ impl Handler {
 		say += "```\n";
 		say += "Admin list:\n```\n";
 		x = 0;
-		for (i, admin) in self.app_data.read().unwrap().admins.iter().enumerate() {
+		for (i, admin) in admins.iter().enumerate() {
 			let n = i + 1;
 			let name = &admin.0;
 			say += format!("{n}. {name}\n").as_str();
@@ -289,17 +873,52 @@ impl Handler {
 		}
 		say += "```";
 		if let Err(why) = msg.channel_id.say(&ctx.http, say).await {
-			println!("Error listing banned words: {why:?}");
+			warn!("Error listing banned words: {why:?}");
 		}
 	}
 }
 
+// Reads a log level (e.g. "debug", "info", "warn") from the env directory,
+// defaulting to `Info`, and wires up a console appender plus a rotating
+// file appender so operators get a durable log trail instead of stdout
+// scrollback.
+fn init_logging() {
+	let level = ENV_PATHS.iter()
+		.find_map(|path| std::fs::read_to_string(Path::new(path).join("loglevel")).ok())
+		.and_then(|s| s.trim().parse::<LevelFilter>().ok())
+		.unwrap_or(LevelFilter::Info);
+
+	let encoder = || Box::new(PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S)} {l} - {m}{n}"));
+
+	let stdout = ConsoleAppender::builder().encoder(encoder()).build();
+
+	let roller = FixedWindowRoller::builder()
+		.build("logs/denpabot.{}.log.gz", 5)
+		.expect("failed to build log roller");
+	let trigger = SizeTrigger::new(10 * 1024 * 1024);
+	let policy = CompoundPolicy::new(Box::new(trigger), Box::new(roller));
+	let rolling = RollingFileAppender::builder()
+		.encoder(encoder())
+		.build("logs/denpabot.log", Box::new(policy))
+		.expect("failed to build rolling file appender");
+
+	let config = Config::builder()
+		.appender(Appender::builder().build("stdout", Box::new(stdout)))
+		.appender(Appender::builder().build("file", Box::new(rolling)))
+		.build(Root::builder().appender("stdout").appender("file").build(level))
+		.expect("failed to build logging config");
+
+	log4rs::init_config(config).expect("failed to initialize logging");
+}
+
 #[tokio::main]
 async fn main() {
+	init_logging();
+
 	let mut handler = Handler::default();
 
-	// hardcoded admin (me)
-	handler.app_data.write().unwrap().admins.push(("mip5".to_string(), 231963552292929546));
+	// hardcoded owner (me)
+	handler.app_data.write().unwrap().owners.push(("mip5".to_string(), 231963552292929546));
 
 	// Configure the client with your Discord bot token in the environment.
 	let mut token: String = "".to_string();
@@ -328,6 +947,73 @@ async fn main() {
 	// Shards will automatically attempt to reconnect, and will perform exponential backoff until
 	// it reconnects.
 	if let Err(why) = client.start().await {
-		println!("Client error: {why:?}");
+		error!("Client error: {why:?}");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn matches_of(words: &[&str], input: &str) -> Vec<(usize, usize)> {
+		let mut trie = Trie::default();
+		for word in words {
+			trie.insert(word);
+		}
+		trie.compute_fail_links();
+		trie.find_matches(input)
+	}
+
+	#[test]
+	fn finds_distinct_words() {
+		let mut matches = matches_of(&["foo", "bar"], "foo and bar");
+		matches.sort();
+		assert_eq!(matches, vec![(0, 3), (8, 11)]);
+	}
+
+	#[test]
+	fn finds_overlapping_suffix_words() {
+		// "she" and "he" both end at the 'e' of "ushers" and "hers" ends at
+		// the final 's'; the "she" state must still surface "he" via its
+		// fail link.
+		let mut matches = matches_of(&["he", "she", "hers"], "ushers");
+		matches.sort();
+		assert_eq!(matches, vec![(1, 4), (2, 4), (2, 6)]);
+	}
+
+	#[test]
+	fn deduplicates_repeated_words() {
+		let matches = matches_of(&["a", "a"], "ca");
+		assert_eq!(matches, vec![(1, 2)]);
+	}
+
+	#[test]
+	fn normalize_offsets_track_original_bytes_through_multibyte_lowering() {
+		// 'İ' (U+0130) lowercases to "i" plus a combining dot above, which is
+		// dropped here as a combining mark; the kept 'i' must still map back
+		// to byte 0 of the original 2-byte 'İ', not the longer lowered copy.
+		let (normalized, offsets) = normalize("İx", &default_leet_map());
+		assert_eq!(normalized, "ix");
+		assert_eq!(offsets, vec![0, 2]);
+	}
+
+	#[test]
+	fn mask_spans_survives_multibyte_lowering_without_panicking() {
+		let leet_map = default_leet_map();
+		let original = "İbad";
+		let (normalized, offsets) = normalize(original, &leet_map);
+		let mut trie = Trie::default();
+		trie.insert("bad");
+		trie.compute_fail_links();
+		let spans = trie.find_matches(&normalized);
+		let masked = mask_spans(original, "***", &offsets, &spans);
+		assert_eq!(masked, "İ***");
+	}
+
+	#[test]
+	fn mask_spans_merges_overlapping_spans() {
+		let offsets: Vec<usize> = (0..=6).collect();
+		let masked = mask_spans("abcdef", "*", &offsets, &[(0, 3), (2, 6)]);
+		assert_eq!(masked, "*");
 	}
 }